@@ -2,24 +2,45 @@
 //!
 //! <https://www.country-files.com/cty-dat-format/>
 
+use std::cell::{Cell, RefCell};
 use std::char;
 use std::io;
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::rc::Rc;
 use std::time::Instant;
 
 use chumsky::prelude::*;
 use chumsky::text::newline;
+use lru::LruCache;
 use patricia_tree::PatriciaMap;
 use tinystr::TinyAsciiStr;
 use tracing::instrument;
 
+/// Default capacity of the callsign resolution cache, see
+/// [`Ctydat::find_country_for_callsign_cached`].
+const DEFAULT_CACHE_CAPACITY: usize = 1000;
+
 #[derive(Debug)]
 pub struct Ctydat {
     /// A trie holding all exact callsigns
     callsign_trie: PatriciaMap<(Rc<Country>, Vec<Override>)>,
     /// A trie holding all callsign prefixes
     prefix_trie: PatriciaMap<(Rc<Country>, Vec<Override>)>,
+    /// All parsed countries, in file order, with their full alias list intact
+    countries: Vec<Rc<Country>>,
+    /// LRU cache memoizing [`Ctydat::find_country_for_callsign_cached`] results
+    cache: RefCell<LruCache<String, Country>>,
+    /// Hit/miss counters for `cache`
+    cache_stats: Cell<CacheStats>,
+}
+
+/// Hit/miss counters for the callsign resolution cache, see
+/// [`Ctydat::find_country_for_callsign_cached`] and [`Ctydat::cache_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
 }
 
 impl Ctydat {
@@ -33,6 +54,12 @@ impl Ctydat {
         })?;
 
         let countries_len = countries.len();
+        // Keep a full copy, alias lists intact, for `to_cty_dat`/`query`/etc.
+        // The copies fed into the tries below have their alias list cleared,
+        // since `Country::implement_overrides` discards it anyway and it
+        // would otherwise be cloned on every lookup for nothing.
+        let full_countries: Vec<Rc<Country>> = countries.iter().cloned().map(Rc::new).collect();
+
         let mut callsign_trie = PatriciaMap::new();
         let mut prefix_trie = PatriciaMap::new();
 
@@ -67,6 +94,11 @@ impl Ctydat {
         Ok(Ctydat {
             callsign_trie,
             prefix_trie,
+            countries: full_countries,
+            cache: RefCell::new(LruCache::new(
+                NonZeroUsize::new(DEFAULT_CACHE_CAPACITY).expect("capacity is non-zero"),
+            )),
+            cache_stats: Cell::new(CacheStats::default()),
         })
     }
 
@@ -80,8 +112,16 @@ impl Ctydat {
 
     #[instrument(skip(self))]
     pub fn find_country_for_callsign(&self, callsign: &str) -> Option<Country> {
-        let ts = Instant::now();
         let callsign = callsign.to_lowercase();
+        self.find_country_for_lowercased_callsign(&callsign)
+    }
+
+    /// Shared lookup logic for [`Ctydat::find_country_for_callsign`] and
+    /// [`Ctydat::find_country_for_callsign_cached`], taking a callsign
+    /// that's already lowercase so neither caller pays for lowercasing
+    /// twice.
+    fn find_country_for_lowercased_callsign(&self, callsign: &str) -> Option<Country> {
+        let ts = Instant::now();
 
         if let Some((country, overrides)) = self.callsign_trie.get(&callsign) {
             let country = country.implement_overrides(overrides);
@@ -109,6 +149,311 @@ impl Ctydat {
         );
         None
     }
+
+    /// Replace the callsign resolution cache with one of the given
+    /// `capacity`, discarding any entries already cached.
+    ///
+    /// Used with [`Ctydat::find_country_for_callsign_cached`]. The cache
+    /// defaults to 1000 entries if this is never called.
+    #[must_use]
+    pub fn with_cache_capacity(self, capacity: usize) -> Ctydat {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Ctydat {
+            cache: RefCell::new(LruCache::new(capacity)),
+            cache_stats: Cell::new(CacheStats::default()),
+            ..self
+        }
+    }
+
+    /// Like [`Ctydat::find_country_for_callsign`], but memoizes the
+    /// resolved [`Country`] in a bounded LRU cache keyed by callsign.
+    ///
+    /// This avoids repeating the trie probes when resolving a large log
+    /// where the same callsigns recur constantly. See
+    /// [`Ctydat::cache_stats`] for hit/miss counters and
+    /// [`Ctydat::with_cache_capacity`] to size the cache.
+    #[instrument(skip(self))]
+    pub fn find_country_for_callsign_cached(&self, callsign: &str) -> Option<Country> {
+        let callsign = callsign.to_lowercase();
+
+        if let Some(country) = self.cache.borrow_mut().get(&callsign) {
+            self.record_cache_hit();
+            return Some(country.clone());
+        }
+        self.record_cache_miss();
+
+        let country = self.find_country_for_lowercased_callsign(&callsign)?;
+        self.cache.borrow_mut().put(callsign, country.clone());
+        Some(country)
+    }
+
+    /// Hit/miss counters for the cache used by
+    /// [`Ctydat::find_country_for_callsign_cached`].
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache_stats.get()
+    }
+
+    fn record_cache_hit(&self) {
+        let mut stats = self.cache_stats.get();
+        stats.hits += 1;
+        self.cache_stats.set(stats);
+    }
+
+    fn record_cache_miss(&self) {
+        let mut stats = self.cache_stats.get();
+        stats.misses += 1;
+        self.cache_stats.set(stats);
+    }
+
+    /// Serialize the parsed country set back to canonical CTY.DAT text,
+    /// re-emitting each record with [`Country::to_cty_dat_record`].
+    pub fn to_cty_dat(&self) -> String {
+        self.countries
+            .iter()
+            .map(|country| country.to_cty_dat_record())
+            .collect()
+    }
+
+    /// Start a [`Query`] over the parsed country set, filtering on zone,
+    /// continent, and coordinate conditions.
+    pub fn query(&self) -> Query<'_> {
+        Query {
+            countries: &self.countries,
+            filter: Filter::All,
+        }
+    }
+
+    /// Find the [`Country`] whose stored coordinates are closest to
+    /// `lat`/`lon` (decimal degrees, positive north/east) by great-circle
+    /// distance.
+    pub fn find_nearest_country(&self, lat: f32, lon: f32) -> Option<Country> {
+        self.countries
+            .iter()
+            .min_by(|a, b| {
+                let distance_a = haversine_distance_km(lat, lon, a.latitude, -a.longitude);
+                let distance_b = haversine_distance_km(lat, lon, b.latitude, -b.longitude);
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|country| (**country).clone())
+    }
+
+    /// Find all countries within `radius_km` of `lat`/`lon` (decimal
+    /// degrees, positive north/east), sorted nearest first.
+    pub fn countries_within(&self, lat: f32, lon: f32, radius_km: f32) -> Vec<Country> {
+        let mut matches: Vec<(f32, Country)> = self
+            .countries
+            .iter()
+            .filter_map(|country| {
+                let distance =
+                    haversine_distance_km(lat, lon, country.latitude, -country.longitude);
+                (distance <= radius_km).then(|| (distance, (**country).clone()))
+            })
+            .collect();
+
+        matches.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        matches.into_iter().map(|(_, country)| country).collect()
+    }
+}
+
+/// Mean Earth radius in kilometres, used by [`haversine_distance_km`].
+const EARTH_RADIUS_KM: f32 = 6371.0;
+
+/// Great-circle distance in kilometres between two points given as decimal
+/// degrees (positive north/east), using the haversine formula.
+fn haversine_distance_km(lat1: f32, lon1: f32, lat2: f32, lon2: f32) -> f32 {
+    let (lat1, lon1, lat2, lon2) = (
+        lat1.to_radians(),
+        lon1.to_radians(),
+        lat2.to_radians(),
+        lon2.to_radians(),
+    );
+    let dlat = lat2 - lat1;
+    let dlon = lon2 - lon1;
+
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+
+    EARTH_RADIUS_KM * c
+}
+
+/// A composable filter over a [`Ctydat`]'s parsed country set, built with
+/// [`Ctydat::query`].
+///
+/// Conditions such as [`Query::continent_eq`] chain together with AND
+/// semantics; use [`Query::or`] to combine with OR. Call [`Query::iter`] to
+/// run the query.
+pub struct Query<'a> {
+    countries: &'a [Rc<Country>],
+    filter: Filter,
+}
+
+impl<'a> Query<'a> {
+    /// Match countries whose continent abbreviation equals `continent`.
+    ///
+    /// A `continent` that isn't a valid 2-letter code (e.g. `"USA"`) can
+    /// never match any [`Country`], so this narrows the query to match
+    /// nothing rather than returning an error.
+    #[must_use]
+    pub fn continent_eq(mut self, continent: &str) -> Query<'a> {
+        let condition = match TinyAsciiStr::from_str(continent) {
+            Ok(continent) => Filter::ContinentEq(continent),
+            Err(_) => Filter::Never,
+        };
+        self.filter = self.filter.and(condition);
+        self
+    }
+
+    /// Match countries whose CQ zone is between `low` and `high`, inclusive.
+    #[must_use]
+    pub fn cq_zone_between(mut self, low: u8, high: u8) -> Query<'a> {
+        self.filter = self.filter.and(Filter::CqZoneBetween(low, high));
+        self
+    }
+
+    /// Match countries whose ITU zone equals `zone`.
+    #[must_use]
+    pub fn itu_zone_eq(mut self, zone: u8) -> Query<'a> {
+        self.filter = self.filter.and(Filter::ItuZoneEq(zone));
+        self
+    }
+
+    /// Match countries whose local time offset from GMT is less than `offset`.
+    #[must_use]
+    pub fn time_offset_lt(mut self, offset: f32) -> Query<'a> {
+        self.filter = self.filter.and(Filter::TimeOffsetLt(offset));
+        self
+    }
+
+    /// Combine this query with an alternative, built by `other` from a fresh
+    /// query over the same country set: matches if either side matches.
+    #[must_use]
+    pub fn or(self, other: impl FnOnce(Query<'a>) -> Query<'a>) -> Query<'a> {
+        let alternative = other(Query {
+            countries: self.countries,
+            filter: Filter::All,
+        });
+        Query {
+            countries: self.countries,
+            filter: Filter::Or(Box::new(self.filter), Box::new(alternative.filter)),
+        }
+    }
+
+    /// Run the query, returning an iterator of matching [`Country`] values.
+    pub fn iter(&self) -> impl Iterator<Item = Country> + '_ {
+        self.countries
+            .iter()
+            .filter(move |country| self.filter.matches(country))
+            .map(|country| (**country).clone())
+    }
+}
+
+enum Filter {
+    All,
+    Never,
+    ContinentEq(TinyAsciiStr<2>),
+    CqZoneBetween(u8, u8),
+    ItuZoneEq(u8),
+    TimeOffsetLt(f32),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    fn and(self, other: Filter) -> Filter {
+        match self {
+            Filter::All => other,
+            filter => Filter::And(Box::new(filter), Box::new(other)),
+        }
+    }
+
+    fn matches(&self, country: &Country) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Never => false,
+            Filter::ContinentEq(continent) => country.continent == *continent,
+            Filter::CqZoneBetween(low, high) => (*low..=*high).contains(&country.cq_zone),
+            Filter::ItuZoneEq(zone) => country.itu_zone == *zone,
+            Filter::TimeOffsetLt(offset) => country.time_offset < *offset,
+            Filter::And(a, b) => a.matches(country) && b.matches(country),
+            Filter::Or(a, b) => a.matches(country) || b.matches(country),
+        }
+    }
+}
+
+/// Operational suffixes that carry no prefix information of their own and
+/// are ignored when deriving a [WPX](https://www.cqwpx.com/rules.htm) prefix.
+const WPX_JUNK_SUFFIXES: &[&str] = &["P", "M", "MM", "AM", "A", "QRP"];
+
+/// Derive the WPX contest prefix of a full callsign.
+///
+/// This follows the rules used by CQ WPX scoring software: the prefix is the
+/// letter/numeral combination that starts the call, extended through its
+/// last digit (or `<letters>0` if the call has no digit at all). A `/`
+/// separated portable designator changes the prefix it attaches to: a lone
+/// digit (`N8BJQ/9` → `N9`) replaces the trailing digit of the base prefix,
+/// while any other designator (`PA0/N8BJQ` → `PA0`) is itself used to derive
+/// the prefix. The operational suffixes `/P`, `/M`, `/MM`, `/AM`, `/A` and
+/// `/QRP`, and any other trailing single-letter suffix, carry no prefix
+/// information and are ignored.
+///
+/// Returns `None` if no valid, non-empty prefix can be derived.
+pub fn wpx_prefix(callsign: &str) -> Option<TinyAsciiStr<8>> {
+    let callsign = callsign.to_ascii_uppercase();
+    let mut parts: Vec<&str> = callsign
+        .split('/')
+        .filter(|segment| !segment.is_empty() && !WPX_JUNK_SUFFIXES.contains(segment))
+        .collect();
+    parts.truncate(2);
+
+    let prefix = match parts.as_slice() {
+        [] => return None,
+        [single] => wpx_base_prefix(single)?,
+        [first, second] => {
+            if second.len() == 1 && second.chars().all(|c| c.is_ascii_digit()) {
+                wpx_replace_trailing_digit(first, second)?
+            } else if second.len() == 1 {
+                wpx_base_prefix(first)?
+            } else {
+                let modifier = if second.len() < first.len() {
+                    second
+                } else {
+                    first
+                };
+                wpx_base_prefix(modifier)?
+            }
+        }
+        _ => unreachable!("parts was truncated to at most 2 elements"),
+    };
+
+    TinyAsciiStr::from_str(&prefix).ok()
+}
+
+/// Derive the prefix of a single (non-portable) callsign segment: every
+/// character up through its last digit, or its first two letters plus `0`
+/// if it has no digit at all.
+fn wpx_base_prefix(segment: &str) -> Option<String> {
+    if segment.is_empty() {
+        return None;
+    }
+
+    if let Some(last_digit) = segment.rfind(|c: char| c.is_ascii_digit()) {
+        Some(segment[..=last_digit].to_string())
+    } else {
+        let mut chars = segment.chars();
+        let first = chars.next()?;
+        let second = chars.next().map(String::from).unwrap_or_default();
+        Some(format!("{first}{second}0"))
+    }
+}
+
+/// Replace the trailing digit of `base`'s WPX prefix with `digits`, e.g.
+/// `N8BJQ` with appended `9` becomes `N9`.
+fn wpx_replace_trailing_digit(base: &str, digits: &str) -> Option<String> {
+    let prefix = wpx_base_prefix(base)?;
+    let last_digit = prefix.rfind(|c: char| c.is_ascii_digit())?;
+    let mut result = prefix[..last_digit].to_string();
+    result.push_str(digits);
+    Some(result)
 }
 
 // Before opts: size = 112, align = 8
@@ -118,7 +463,7 @@ impl Ctydat {
 /// Single country from CTY.DAT file
 ///
 /// <https://www.country-files.com/cty-dat-format/>
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Country {
     /// Country Name
     pub country_name: String,
@@ -163,10 +508,111 @@ impl Country {
         }
         country
     }
+
+    /// Serialize this country back to its canonical CTY.DAT text record
+    /// (header line plus alias list), including a trailing newline.
+    ///
+    /// The inverse of the per-country grammar parsed by [`Ctydat::from_str`].
+    pub fn to_cty_dat_record(&self) -> String {
+        let aliases: Vec<String> = self
+            .prefix_list
+            .iter()
+            .map(Prefix::to_cty_dat_token)
+            .collect();
+
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}:\n    {};\n",
+            self.country_name,
+            self.cq_zone,
+            self.itu_zone,
+            self.continent,
+            self.latitude,
+            self.longitude,
+            self.time_offset,
+            self.primary_prefix,
+            aliases.join(","),
+        )
+    }
+
+    /// Render this country's stored coordinates as an
+    /// [RFC 1876](https://www.rfc-editor.org/rfc/rfc1876) LOC master-file
+    /// text record, e.g. `61 22 48.0 N 24 49 12.0 E 0.00m 1m 10000m 10m`.
+    ///
+    /// Seconds are rounded to one decimal place, since CTY.DAT's `f32`
+    /// coordinates aren't precise enough to support the RFC's usual
+    /// millisecond-of-arc resolution.
+    ///
+    /// Altitude is always `0.00m` and size/precision use the RFC's default
+    /// values, since CTY.DAT carries no such data.
+    pub fn to_dns_loc(&self) -> String {
+        let (lat_deg, lat_min, lat_sec, lat_hemisphere) = dms(self.latitude, 'N', 'S');
+        // The file stores longitude as positive-for-West.
+        let (lon_deg, lon_min, lon_sec, lon_hemisphere) = dms(-self.longitude, 'E', 'W');
+
+        format!(
+            "{lat_deg} {lat_min} {lat_sec:.1} {lat_hemisphere} \
+             {lon_deg} {lon_min} {lon_sec:.1} {lon_hemisphere} \
+             0.00m 1m 10000m 10m"
+        )
+    }
+
+    /// Pack this country's stored coordinates as an RFC 1876 LOC version 0
+    /// wire-format record: a version byte, size/horizontal/vertical
+    /// precision bytes, then latitude, longitude and altitude each as a
+    /// big-endian `u32` offset from the equator, prime meridian and
+    /// reference altitude respectively.
+    pub fn to_dns_loc_wire(&self) -> Vec<u8> {
+        const SIZE: u8 = 0x12; // 1m, base 1 * 10^2 cm
+        const HORIZ_PRE: u8 = 0x16; // 10000m, base 1 * 10^6 cm
+        const VERT_PRE: u8 = 0x13; // 10m, base 1 * 10^3 cm
+        const EQUATOR: f64 = 2_147_483_648.0; // 2^31
+        const ALTITUDE_ZERO_CM: u32 = 10_000_000; // 0m above the RFC 1876 reference
+
+        let latitude = (EQUATOR + f64::from(self.latitude) * 3_600_000.0) as u32;
+        // The file stores longitude as positive-for-West.
+        let longitude = (EQUATOR + f64::from(-self.longitude) * 3_600_000.0) as u32;
+
+        let mut wire = Vec::with_capacity(16);
+        wire.push(0); // version
+        wire.push(SIZE);
+        wire.push(HORIZ_PRE);
+        wire.push(VERT_PRE);
+        wire.extend_from_slice(&latitude.to_be_bytes());
+        wire.extend_from_slice(&longitude.to_be_bytes());
+        wire.extend_from_slice(&ALTITUDE_ZERO_CM.to_be_bytes());
+        wire
+    }
+}
+
+/// Split a signed decimal-degrees value into degrees/minutes/seconds plus a
+/// hemisphere letter, used by [`Country::to_dns_loc`].
+fn dms(
+    decimal: f32,
+    positive_hemisphere: char,
+    negative_hemisphere: char,
+) -> (u32, u32, f32, char) {
+    let hemisphere = if decimal >= 0.0 {
+        positive_hemisphere
+    } else {
+        negative_hemisphere
+    };
+    let decimal = decimal.abs();
+
+    // Round to the nearest tenth of an arcsecond *before* splitting into
+    // degrees/minutes/seconds, so a carry (e.g. 59.97" rounding up to
+    // 60.0") propagates into minutes/degrees instead of producing an
+    // invalid `seconds >= 60` field once the caller formats it to one
+    // decimal place.
+    let total_tenths = (f64::from(decimal) * 3_600.0 * 10.0).round() as u64;
+    let degrees = (total_tenths / 36_000) as u32;
+    let remainder = total_tenths % 36_000;
+    let minutes = (remainder / 600) as u32;
+    let seconds = (remainder % 600) as f32 / 10.0;
+    (degrees, minutes, seconds, hemisphere)
 }
 
 /// A single prefix or exact callsign
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Prefix {
     /// A single prefix
     // Longest found from dataset was 13 chars (A60STAYHOME/1)
@@ -175,11 +621,35 @@ pub enum Prefix {
     Prefix(TinyAsciiStr<8>, Option<Vec<Override>>),
 }
 
+impl Prefix {
+    /// Render this alias the way [`Country::to_cty_dat_record`] writes it:
+    /// an `=` marker for exact callsigns followed by any [`Override`]s in
+    /// their bracket syntax.
+    fn to_cty_dat_token(&self) -> String {
+        match self {
+            Prefix::Callsign(callsign, overrides) => {
+                format!("={callsign}{}", render_overrides(overrides))
+            }
+            Prefix::Prefix(prefix, overrides) => {
+                format!("{prefix}{}", render_overrides(overrides))
+            }
+        }
+    }
+}
+
+fn render_overrides(overrides: &Option<Vec<Override>>) -> String {
+    overrides
+        .iter()
+        .flatten()
+        .map(Override::to_cty_dat_suffix)
+        .collect()
+}
+
 /// A [Country] prefix alias list (see [Country::prefix_list]) can include
 /// overrides to some data in [Country]. These are the supported overrides that
 /// can be implemented into [Country] with calling
 /// [Country::implement_overrides] method.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Override {
     CqZone(u8),
     ItuZone(u8),
@@ -188,6 +658,20 @@ pub enum Override {
     TimeOffset(f32),
 }
 
+impl Override {
+    /// Render this override in its CTY.DAT bracket syntax, e.g. `[15]` for a
+    /// CQ zone override.
+    fn to_cty_dat_suffix(&self) -> String {
+        match self {
+            Override::CqZone(zone) => format!("[{zone}]"),
+            Override::ItuZone(zone) => format!("({zone})"),
+            Override::Coordinates(lat, lon) => format!("<{lat}/{lon}>"),
+            Override::Continent(continent) => format!("{{{continent}}}"),
+            Override::TimeOffset(offset) => format!("~{offset}~"),
+        }
+    }
+}
+
 fn parser() -> impl Parser<char, Vec<Country>, Error = Simple<char>> {
     let ascii_not_comma = |c: &char| c.is_ascii() && !c.is_control() && *c != ':';
     let ascii_float = |c: &char| c.is_ascii_digit() || *c == '-' || *c == '.';
@@ -393,4 +877,105 @@ mod tests {
         assert_eq!(cty.longitude, -24.82);
         assert_eq!(cty.time_offset, -2.0);
     }
+
+    #[test]
+    fn test_wpx_prefix() {
+        use crate::wpx_prefix;
+
+        assert_eq!(wpx_prefix("WB8ZRL").unwrap().as_str(), "WB8");
+        assert_eq!(wpx_prefix("HG1S").unwrap().as_str(), "HG1");
+        assert_eq!(wpx_prefix("XEFTJ").unwrap().as_str(), "XE0");
+        assert_eq!(wpx_prefix("N8BJQ/9").unwrap().as_str(), "N9");
+        assert_eq!(wpx_prefix("N8BJQ/99").unwrap().as_str(), "99");
+        assert_eq!(wpx_prefix("PA0/N8BJQ").unwrap().as_str(), "PA0");
+        assert_eq!(wpx_prefix("W3ABC/P").unwrap().as_str(), "W3");
+        assert!(wpx_prefix("").is_none());
+    }
+
+    #[test]
+    fn test_find_country_for_callsign_cached() {
+        let ctydat = crate::Ctydat::from_str(FINLAND).unwrap();
+
+        assert_eq!(ctydat.cache_stats(), crate::CacheStats::default());
+
+        let first = ctydat.find_country_for_callsign_cached("OH1AD").unwrap();
+        assert_eq!(ctydat.cache_stats().misses, 1);
+        assert_eq!(ctydat.cache_stats().hits, 0);
+
+        let second = ctydat.find_country_for_callsign_cached("OH1AD").unwrap();
+        assert_eq!(ctydat.cache_stats().misses, 1);
+        assert_eq!(ctydat.cache_stats().hits, 1);
+        assert_eq!(first.country_name, second.country_name);
+    }
+
+    #[test]
+    fn test_to_cty_dat_round_trip() {
+        let original = parser().parse(FINLAND).unwrap();
+        let finland = original.first().unwrap();
+
+        let serialized = finland.to_cty_dat_record();
+        let reparsed = parser().parse(serialized.as_str()).unwrap();
+        let reparsed = reparsed.first().unwrap();
+
+        assert_eq!(finland, reparsed);
+    }
+
+    #[test]
+    fn test_query() {
+        let ctydat = crate::Ctydat::from_str(FINLAND).unwrap();
+
+        let eu: Vec<_> = ctydat.query().continent_eq("EU").iter().collect();
+        assert_eq!(eu.len(), 1);
+        assert_eq!(eu[0].country_name, "Finland");
+
+        assert_eq!(ctydat.query().continent_eq("NA").iter().count(), 0);
+        assert_eq!(ctydat.query().cq_zone_between(14, 16).iter().count(), 1);
+        assert_eq!(ctydat.query().itu_zone_eq(18).iter().count(), 1);
+        assert_eq!(ctydat.query().time_offset_lt(0.0).iter().count(), 1);
+        assert_eq!(
+            ctydat
+                .query()
+                .continent_eq("NA")
+                .or(|q| q.continent_eq("EU"))
+                .iter()
+                .count(),
+            1
+        );
+
+        // An invalid continent code never matches, it does not panic.
+        assert_eq!(ctydat.query().continent_eq("USA").iter().count(), 0);
+    }
+
+    #[test]
+    fn test_find_nearest_country() {
+        let ctydat = crate::Ctydat::from_str(FINLAND).unwrap();
+
+        // Helsinki, Finland: roughly 60.17N, 24.94E.
+        let nearest = ctydat.find_nearest_country(60.17, 24.94).unwrap();
+        assert_eq!(nearest.country_name, "Finland");
+
+        let within = ctydat.countries_within(60.17, 24.94, 500.0);
+        assert_eq!(within.len(), 1);
+        assert_eq!(within[0].country_name, "Finland");
+
+        assert!(ctydat.countries_within(60.17, 24.94, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_to_dns_loc() {
+        let ctydat = parser().parse(FINLAND).unwrap();
+        let finland = ctydat.first().unwrap();
+
+        assert_eq!(
+            finland.to_dns_loc(),
+            "61 22 48.0 N 24 49 12.0 E 0.00m 1m 10000m 10m"
+        );
+
+        let wire = finland.to_dns_loc_wire();
+        assert_eq!(wire.len(), 16);
+        assert_eq!(wire[0], 0, "version byte must be 0");
+        assert_eq!(wire[1], 0x12);
+        assert_eq!(wire[2], 0x16);
+        assert_eq!(wire[3], 0x13);
+    }
 }